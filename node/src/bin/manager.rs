@@ -24,7 +24,10 @@ use graph_node::manager::color::Terminal;
 use graph_node::manager::commands;
 use graph_node::network_setup::Networks;
 use graph_node::{
-    manager::{deployment::DeploymentSearch, PanicSubscriptionManager},
+    manager::{
+        deployment::{DeploymentFilter, DeploymentOrder, DeploymentSearch, Paging},
+        PanicSubscriptionManager,
+    },
     store_builder::StoreBuilder,
     MetricsContext,
 };
@@ -123,7 +126,9 @@ pub enum Command {
     /// The deployment can be specified as either a subgraph name, an IPFS
     /// hash `Qm..`, or the database namespace `sgdNNN`. Since the same IPFS
     /// hash can be deployed in multiple shards, it is possible to specify
-    /// the shard by adding `:shard` to the IPFS hash.
+    /// the shard by adding `:shard` to the IPFS hash. A subgraph name
+    /// matches every subgraph whose name contains it; prefix it with `=`
+    /// to match only the subgraph with exactly that name.
     Info {
         /// The deployment (see above)
         deployment: Option<DeploymentSearch>,
@@ -142,6 +147,24 @@ pub enum Command {
         /// List only used (current and pending) versions
         #[clap(long, short)]
         used: bool,
+        /// List only deployments of this chain
+        #[clap(long)]
+        chain: Option<String>,
+        /// List only deployments in this shard
+        #[clap(long)]
+        shard: Option<String>,
+        /// List only deployments assigned to this node
+        #[clap(long)]
+        node: Option<String>,
+        /// Sort deployments by `id` or by subgraph `name`
+        #[clap(long, default_value = "id")]
+        sort: DeploymentOrder,
+        /// List at most this many deployments [default: 1000]
+        #[clap(long)]
+        limit: Option<u32>,
+        /// Skip this many deployments before listing any
+        #[clap(long)]
+        offset: Option<u32>,
     },
     /// Manage unused deployments
     ///
@@ -332,7 +355,9 @@ pub enum Command {
     /// The deployment can be specified as either a subgraph name, an IPFS
     /// hash `Qm..`, or the database namespace `sgdNNN`. Since the same IPFS
     /// hash can be deployed in multiple shards, it is possible to specify
-    /// the shard by adding `:shard` to the IPFS hash.
+    /// the shard by adding `:shard` to the IPFS hash. A subgraph name
+    /// matches every subgraph whose name contains it; prefix it with `=`
+    /// to match only the subgraph with exactly that name.
     Drop {
         /// The deployment identifier
         deployment: DeploymentSearch,
@@ -1122,7 +1147,26 @@ async fn main() -> anyhow::Result<()> {
             status,
             used,
             all,
+            chain,
+            shard,
+            node,
+            sort,
+            limit,
+            offset,
         } => {
+            let filter = DeploymentFilter {
+                current,
+                pending,
+                used,
+                chain,
+                shard,
+                node,
+            };
+            let paging = Paging {
+                order: sort,
+                limit,
+                offset,
+            };
             let (primary, store) = if status {
                 let (store, primary) = ctx.store_and_primary();
                 (primary, Some(store))
@@ -1132,13 +1176,12 @@ async fn main() -> anyhow::Result<()> {
 
             match deployment {
                 Some(deployment) => {
-                    commands::info::run(primary, store, deployment, current, pending, used).err();
+                    commands::info::run(primary, store, deployment, filter, paging).err();
                 }
                 None => {
                     if all {
                         let deployment = DeploymentSearch::All;
-                        commands::info::run(primary, store, deployment, current, pending, used)
-                            .err();
+                        commands::info::run(primary, store, deployment, filter, paging).err();
                     } else {
                         bail!("Please specify a deployment or use --all to list all deployments");
                     }
@@ -76,7 +76,7 @@ pub async fn run(
     };
 
     let name = match search {
-        DeploymentSearch::Name { name } => name,
+        DeploymentSearch::Name { name } | DeploymentSearch::ExactName { name } => name,
         _ => bail!("The `name` must be a valid subgraph name"),
     };
 
@@ -1,5 +1,5 @@
 use crate::manager::{
-    deployment::{Deployment, DeploymentSearch},
+    deployment::{Deployment, DeploymentFilter, DeploymentSearch, Paging},
     display::List,
     prompt::prompt_for_confirmation,
 };
@@ -22,7 +22,13 @@ pub async fn run(
     skip_confirmation: bool,
 ) -> anyhow::Result<()> {
     // call `graphman info` to find matching deployments
-    let deployments = search_term.find(primary_pool.clone(), current, pending, used)?;
+    let filter = DeploymentFilter {
+        current,
+        pending,
+        used,
+        ..DeploymentFilter::default()
+    };
+    let deployments = search_term.find(primary_pool.clone(), &filter, &Paging::default())?;
     if deployments.is_empty() {
         bail!("Found no deployment for search_term: {search_term}")
     } else {
@@ -3,17 +3,27 @@ use std::sync::Arc;
 use graph::{components::store::StatusStore, data::subgraph::status, prelude::anyhow};
 use graph_store_postgres::{connection_pool::ConnectionPool, Store};
 
-use crate::manager::deployment::{Deployment, DeploymentSearch};
+use crate::manager::deployment::{Deployment, DeploymentFilter, DeploymentSearch, Paging};
+
+/// The most deployments that are listed if no `--limit` is given
+const MAX_DEPLOYMENTS: u32 = 1000;
 
 pub fn run(
     pool: ConnectionPool,
     store: Option<Arc<Store>>,
     search: DeploymentSearch,
-    current: bool,
-    pending: bool,
-    used: bool,
+    filter: DeploymentFilter,
+    paging: Paging,
 ) -> Result<(), anyhow::Error> {
-    let deployments = search.find(pool, current, pending, used)?;
+    let (paging, max) = paging.capped(MAX_DEPLOYMENTS);
+    let mut deployments = search.find(pool, &filter, &paging)?;
+    let truncated = match max {
+        Some(max) if deployments.len() > max => {
+            deployments.truncate(max);
+            true
+        }
+        _ => false,
+    };
     let ids: Vec<_> = deployments.iter().map(|d| d.locator().id).collect();
     let statuses = match store {
         Some(store) => store.status(status::Filter::DeploymentIds(ids))?,
@@ -24,6 +34,12 @@ pub fn run(
         println!("No matches");
     } else {
         Deployment::print_table(deployments, statuses);
+        if truncated {
+            println!(
+                "Only {} deployments are listed, use --limit and --offset to see more",
+                MAX_DEPLOYMENTS
+            );
+        }
     }
     Ok(())
 }
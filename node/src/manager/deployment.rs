@@ -1,9 +1,10 @@
-use std::collections::HashSet;
 use std::fmt;
 use std::str::FromStr;
 
-use diesel::{dsl::sql, prelude::*};
-use diesel::{sql_types::Text, PgConnection};
+use diesel::expression::SqlLiteral;
+use diesel::pg::Pg;
+use diesel::sql_types::Text;
+use diesel::{dsl, dsl::sql, prelude::*, PgConnection};
 
 use graph::components::store::DeploymentId;
 use graph::{
@@ -14,6 +15,10 @@ use graph::{
 use graph_store_postgres::command_support::catalog as store_catalog;
 use graph_store_postgres::connection_pool::ConnectionPool;
 use graph_store_postgres::unused;
+use store_catalog::deployment_schemas as ds;
+use store_catalog::subgraph as s;
+use store_catalog::subgraph_deployment_assignment as a;
+use store_catalog::subgraph_version as v;
 
 use crate::manager::display::List;
 
@@ -24,13 +29,51 @@ lazy_static! {
     static ref DEPLOYMENT_RE: Regex = Regex::new("\\A(?P<nsp>sgd[0-9]+)\\z").unwrap();
 }
 
+/// SQL expression for the status of a subgraph version: `pending`,
+/// `current`, or `unused`
+const VERSION_STATUS: &str = "(case
+    when subgraphs.subgraph.pending_version = subgraphs.subgraph_version.id then 'pending'
+    when subgraphs.subgraph.current_version = subgraphs.subgraph_version.id then 'current'
+    else 'unused' end)";
+
+/// The tables that are joined to look up deployments
+type DeploymentSource = dsl::LeftJoinOn<
+    dsl::InnerJoinOn<
+        dsl::InnerJoinOn<ds::table, v::table, dsl::Eq<v::deployment, ds::subgraph>>,
+        s::table,
+        dsl::Eq<v::subgraph, s::id>,
+    >,
+    a::table,
+    dsl::Eq<a::id, ds::id>,
+>;
+
+/// The columns that are loaded into a [`Deployment`]
+type DeploymentSelection = (
+    s::name,
+    SqlLiteral<Text>,
+    v::deployment,
+    ds::name,
+    ds::id,
+    dsl::Nullable<a::node_id>,
+    ds::shard,
+    ds::network,
+    ds::active,
+);
+
+/// A query that loads the [`Deployment`]s matching a [`DeploymentSearch`]
+type DeploymentQuery<'a> =
+    dsl::IntoBoxed<'a, dsl::Select<DeploymentSource, DeploymentSelection>, Pg>;
+
 /// A search for one or multiple deployments to make it possible to search
 /// by subgraph name, IPFS hash, or namespace. Since there can be multiple
 /// deployments for the same IPFS hash, the search term for a hash can
-/// optionally specify a shard.
+/// optionally specify a shard. A subgraph name matches all subgraphs whose
+/// name contains it, unless it is prefixed with `=`, in which case only the
+/// subgraph with exactly that name matches.
 #[derive(Clone, Debug)]
 pub enum DeploymentSearch {
     Name { name: String },
+    ExactName { name: String },
     Hash { hash: String, shard: Option<String> },
     All,
     Deployment { namespace: String },
@@ -40,6 +83,7 @@ impl fmt::Display for DeploymentSearch {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             DeploymentSearch::Name { name } => write!(f, "{}", name),
+            DeploymentSearch::ExactName { name } => write!(f, "={}", name),
             DeploymentSearch::Hash {
                 hash,
                 shard: Some(shard),
@@ -55,7 +99,11 @@ impl FromStr for DeploymentSearch {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Some(caps) = HASH_RE.captures(s) {
+        if let Some(name) = s.strip_prefix('=') {
+            Ok(DeploymentSearch::ExactName {
+                name: name.to_string(),
+            })
+        } else if let Some(caps) = HASH_RE.captures(s) {
             let hash = caps.name("hash").unwrap().as_str().to_string();
             let shard = caps.name("shard").map(|shard| shard.as_str().to_string());
             Ok(DeploymentSearch::Hash { hash, shard })
@@ -70,10 +118,115 @@ impl FromStr for DeploymentSearch {
     }
 }
 
+/// The order in which [`DeploymentSearch::find`] returns deployments
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DeploymentOrder {
+    /// By deployment id, i.e., in the order in which they were created
+    #[default]
+    Id,
+    /// By subgraph name, and by deployment id for the same name
+    Name,
+}
+
+impl FromStr for DeploymentOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "id" => Ok(DeploymentOrder::Id),
+            "name" => Ok(DeploymentOrder::Name),
+            _ => Err(anyhow::anyhow!(
+                "invalid sort order `{}`, must be either `id` or `name`",
+                s
+            )),
+        }
+    }
+}
+
+/// Restricts the deployments that [`DeploymentSearch::find`] returns by
+/// the status of their subgraph version, their chain, their shard, and the
+/// node they are assigned to
+#[derive(Clone, Debug, Default)]
+pub struct DeploymentFilter {
+    /// Only current versions
+    pub current: bool,
+    /// Only pending versions
+    pub pending: bool,
+    /// Only current and pending versions
+    pub used: bool,
+    pub chain: Option<String>,
+    pub shard: Option<String>,
+    pub node: Option<String>,
+}
+
+impl DeploymentFilter {
+    fn apply<'a>(&'a self, query: DeploymentQuery<'a>) -> DeploymentQuery<'a> {
+        let mut query = query;
+        if let Some(chain) = &self.chain {
+            query = query.filter(ds::network.eq(chain));
+        }
+        if let Some(shard) = &self.shard {
+            query = query.filter(ds::shard.eq(shard));
+        }
+        if let Some(node) = &self.node {
+            query = query.filter(a::node_id.eq(node));
+        }
+        query
+    }
+}
+
+/// Which page of the deployments matching a search [`DeploymentSearch::find`]
+/// returns. Ordering, limit and offset are applied in the database
+#[derive(Clone, Debug, Default)]
+pub struct Paging {
+    pub order: DeploymentOrder,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+impl Paging {
+    /// If no limit is set, limit the page to `max` deployments. The
+    /// returned paging fetches one more deployment than that so that the
+    /// caller can tell whether the list was cut short; the second element
+    /// is the number of deployments to keep in that case
+    pub fn capped(&self, max: u32) -> (Paging, Option<usize>) {
+        match self.limit {
+            Some(_) => (self.clone(), None),
+            None => {
+                let paging = Paging {
+                    limit: Some(max + 1),
+                    ..self.clone()
+                };
+                (paging, Some(max as usize))
+            }
+        }
+    }
+
+    fn apply<'a>(&self, query: DeploymentQuery<'a>) -> DeploymentQuery<'a> {
+        // A deployment is returned once for each subgraph version that
+        // uses it; ordering by the version last makes the order total so
+        // that pages neither skip nor repeat rows
+        let query = match self.order {
+            DeploymentOrder::Id => query.order_by((ds::id, v::id)),
+            DeploymentOrder::Name => query.order_by((s::name, ds::id, v::id)),
+        };
+        let query = match self.limit {
+            Some(limit) => query.limit(limit.into()),
+            None => query,
+        };
+        match self.offset {
+            Some(offset) => query.offset(offset.into()),
+            None => query,
+        }
+    }
+}
+
 impl DeploymentSearch {
     pub fn to_unused_filter(self, existing: bool) -> unused::Filter {
         match self {
-            DeploymentSearch::Name { name } => unused::Filter::Name(name),
+            DeploymentSearch::Name { name } | DeploymentSearch::ExactName { name } => {
+                unused::Filter::Name(name)
+            }
             DeploymentSearch::Hash { hash, shard: _ } => unused::Filter::Hash(hash),
             DeploymentSearch::All => {
                 if existing {
@@ -95,23 +248,20 @@ impl DeploymentSearch {
         &self,
         conn: &mut PgConnection,
     ) -> Result<Vec<Deployment>, anyhow::Error> {
-        use store_catalog::deployment_schemas as ds;
-        use store_catalog::subgraph as s;
-        use store_catalog::subgraph_deployment_assignment as a;
-        use store_catalog::subgraph_version as v;
+        Ok(self.query(&[]).load(conn)?)
+    }
 
-        let query = ds::table
+    /// Build the query for deployments matching this search whose version
+    /// status is one of `statuses`. If `statuses` is empty, deployments are
+    /// returned regardless of their status
+    fn query<'a>(&'a self, statuses: &[&'a str]) -> DeploymentQuery<'a> {
+        let mut query = ds::table
             .inner_join(v::table.on(v::deployment.eq(ds::subgraph)))
             .inner_join(s::table.on(v::subgraph.eq(s::id)))
             .left_outer_join(a::table.on(a::id.eq(ds::id)))
             .select((
                 s::name,
-                sql::<Text>(
-                    "(case
-                    when subgraphs.subgraph.pending_version = subgraphs.subgraph_version.id then 'pending'
-                    when subgraphs.subgraph.current_version = subgraphs.subgraph_version.id then 'current'
-                    else 'unused' end) status",
-                ),
+                sql::<Text>(&format!("{} status", VERSION_STATUS)),
                 v::deployment,
                 ds::name,
                 ds::id,
@@ -119,72 +269,112 @@ impl DeploymentSearch {
                 ds::shard,
                 ds::network,
                 ds::active,
-            ));
+            ))
+            .into_boxed();
+
+        if !statuses.is_empty() {
+            query = query.filter(sql::<Text>(VERSION_STATUS).eq_any(statuses.to_vec()));
+        }
 
-        let deployments: Vec<Deployment> = match self {
+        match self {
             DeploymentSearch::Name { name } => {
-                let pattern = format!("%{}%", name);
-                query.filter(s::name.ilike(&pattern)).load(conn)?
+                let pattern = format!("%{}%", escape_like(name));
+                query.filter(s::name.ilike(pattern).escape('\\'))
             }
+            DeploymentSearch::ExactName { name } => query.filter(s::name.eq(name)),
             DeploymentSearch::Hash { hash, shard } => {
-                let query = query.filter(ds::subgraph.eq(&hash));
+                let query = query.filter(ds::subgraph.eq(hash));
                 match shard {
-                    Some(shard) => query.filter(ds::shard.eq(shard)).load(conn)?,
-                    None => query.load(conn)?,
+                    Some(shard) => query.filter(ds::shard.eq(shard)),
+                    None => query,
                 }
             }
-            DeploymentSearch::Deployment { namespace } => {
-                query.filter(ds::name.eq(&namespace)).load(conn)?
-            }
-            DeploymentSearch::All => query.load(conn)?,
-        };
-        Ok(deployments)
+            DeploymentSearch::Deployment { namespace } => query.filter(ds::name.eq(namespace)),
+            DeploymentSearch::All => query,
+        }
     }
 
-    /// Finds all [`Deployment`]s for this [`DeploymentSearch`].
+    /// Finds all [`Deployment`]s for this [`DeploymentSearch`] that pass
+    /// `filter`, on the page described by `paging`.
     pub fn find(
         &self,
         pool: ConnectionPool,
-        current: bool,
-        pending: bool,
-        used: bool,
+        filter: &DeploymentFilter,
+        paging: &Paging,
     ) -> Result<Vec<Deployment>, anyhow::Error> {
-        let current = current || used;
-        let pending = pending || used;
-
-        let deployments = self.lookup(&pool)?;
-        // Filter by status; if neither `current` or `pending` are set, list
-        // all deployments
-        let deployments: Vec<_> = deployments
-            .into_iter()
-            .filter(|deployment| match (current, pending) {
-                (true, false) => deployment.status == "current",
-                (false, true) => deployment.status == "pending",
-                (true, true) => deployment.status == "current" || deployment.status == "pending",
-                (false, false) => true,
-            })
-            .collect();
-        Ok(deployments)
+        let statuses = version_statuses(filter.current, filter.pending, filter.used);
+        let query = filter.apply(self.query(&statuses));
+        let mut conn = pool.get()?;
+        Ok(paging.apply(query).load(&mut conn)?)
     }
 
     /// Finds a single deployment locator for the given deployment identifier.
+    /// If several deployments match, the error lists them so the search
+    /// can be narrowed down
     pub fn locate_unique(&self, pool: &ConnectionPool) -> anyhow::Result<DeploymentLocator> {
-        let mut locators: Vec<DeploymentLocator> = HashSet::<DeploymentLocator>::from_iter(
-            self.lookup(pool)?
-                .into_iter()
-                .map(|deployment| deployment.locator()),
-        )
-        .into_iter()
-        .collect();
-        let deployment_locator = match locators.len() {
+        let deployments = unique_deployments(self.lookup(pool)?);
+
+        match deployments.len() {
             0 => anyhow::bail!("Found no deployment for `{}`", self),
-            1 => locators.pop().unwrap(),
-            n => anyhow::bail!("Found {} deployments for `{}`", n, self),
-        };
-        Ok(deployment_locator)
+            1 => Ok(deployments[0].locator()),
+            n => anyhow::bail!(
+                "Found {} deployments for `{}`, use one of these namespaces instead:\n{}",
+                n,
+                self,
+                list_candidates(&deployments)
+            ),
+        }
+    }
+}
+
+/// Remove duplicates from `deployments`; the same deployment is returned
+/// once for each subgraph version that uses it
+fn unique_deployments(mut deployments: Vec<Deployment>) -> Vec<Deployment> {
+    deployments.sort_by_key(|deployment| deployment.id);
+    deployments.dedup_by_key(|deployment| deployment.id);
+    deployments
+}
+
+/// List `deployments`, one per line, so that the user can pick one of
+/// them when a search is ambiguous
+fn list_candidates(deployments: &[Deployment]) -> String {
+    deployments
+        .iter()
+        .map(|deployment| {
+            format!(
+                "  {} ({}, shard: {}, name: {})",
+                deployment.namespace, deployment.deployment, deployment.shard, deployment.name
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The version statuses that `find` restricts deployments to. If neither
+/// `current` or `pending` are set, the list is empty and deployments with
+/// any status are found
+fn version_statuses(current: bool, pending: bool, used: bool) -> Vec<&'static str> {
+    match (current || used, pending || used) {
+        (true, false) => vec!["current"],
+        (false, true) => vec!["pending"],
+        (true, true) => vec!["current", "pending"],
+        (false, false) => vec![],
     }
 }
 
+/// Escape the characters that have a special meaning in a `LIKE` pattern
+/// so that `s` is matched literally
+fn escape_like(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
 #[derive(Queryable, PartialEq, Eq, Hash, Debug)]
 pub struct Deployment {
     pub name: String,
@@ -273,3 +463,219 @@ impl Deployment {
         list.render();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use diesel::debug_query;
+    use diesel::pg::Pg;
+
+    use super::{
+        escape_like, list_candidates, unique_deployments, version_statuses, Deployment,
+        DeploymentFilter, DeploymentOrder, DeploymentSearch, Paging, VERSION_STATUS,
+    };
+
+    fn deployment(id: i32, name: &str, shard: &str) -> Deployment {
+        Deployment {
+            name: name.to_string(),
+            status: "current".to_string(),
+            deployment: "QmHash".to_string(),
+            namespace: format!("sgd{}", id),
+            id,
+            node_id: None,
+            shard: shard.to_string(),
+            chain: "mainnet".to_string(),
+            active: id == 1,
+        }
+    }
+
+    #[test]
+    fn ambiguous_search_lists_candidates() {
+        // `sgd2` is used by two subgraph versions and therefore returned
+        // twice by a search
+        let deployments = unique_deployments(vec![
+            deployment(2, "b/copy", "shard_a"),
+            deployment(1, "a/original", "primary"),
+            deployment(2, "c/copy", "shard_a"),
+        ]);
+        assert_eq!(
+            vec![1, 2],
+            deployments.iter().map(|d| d.id).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "  sgd1 (QmHash, shard: primary, name: a/original)\n  \
+             sgd2 (QmHash, shard: shard_a, name: b/copy)",
+            list_candidates(&deployments)
+        );
+    }
+
+    #[test]
+    fn find_filters_version_status_in_sql() {
+        let statuses = version_statuses(true, false, false);
+        let search = DeploymentSearch::All;
+        let query = search.query(&statuses);
+        let sql = debug_query::<Pg, _>(&query).to_string();
+        assert!(
+            sql.contains(&format!("WHERE ({} = ANY($1))", VERSION_STATUS)),
+            "{}",
+            sql
+        );
+        assert!(sql.ends_with(r#"-- binds: [["current"]]"#), "{}", sql);
+
+        let statuses = version_statuses(false, false, false);
+        let query = search.query(&statuses);
+        let sql = debug_query::<Pg, _>(&query).to_string();
+        assert!(!sql.contains("WHERE"), "{}", sql);
+        assert!(sql.ends_with("-- binds: []"), "{}", sql);
+    }
+
+    #[test]
+    fn find_filters_chain_shard_and_node_in_sql() {
+        let search = DeploymentSearch::All;
+        let filter = DeploymentFilter {
+            chain: Some("mainnet".to_string()),
+            shard: Some("shard_a".to_string()),
+            node: Some("index_node_1".to_string()),
+            ..DeploymentFilter::default()
+        };
+        let query = filter.apply(search.query(&[]));
+        let sql = debug_query::<Pg, _>(&query).to_string();
+        assert!(
+            sql.ends_with(
+                r#"WHERE ((("deployment_schemas"."network" = $1) AND ("deployment_schemas"."shard" = $2)) AND ("subgraphs"."subgraph_deployment_assignment"."node_id" = $3)) -- binds: ["mainnet", "shard_a", "index_node_1"]"#
+            ),
+            "{}",
+            sql
+        );
+
+        let filter = DeploymentFilter {
+            shard: Some("shard_a".to_string()),
+            ..DeploymentFilter::default()
+        };
+        let query = filter.apply(search.query(&[]));
+        let sql = debug_query::<Pg, _>(&query).to_string();
+        assert!(
+            sql.ends_with(r#"WHERE ("deployment_schemas"."shard" = $1) -- binds: ["shard_a"]"#),
+            "{}",
+            sql
+        );
+
+        let filter = DeploymentFilter::default();
+        let query = filter.apply(search.query(&[]));
+        let sql = debug_query::<Pg, _>(&query).to_string();
+        assert!(!sql.contains("WHERE"), "{}", sql);
+    }
+
+    #[test]
+    fn find_pages_in_sql() {
+        let search = DeploymentSearch::All;
+
+        let query = Paging::default().apply(search.query(&[]));
+        let sql = debug_query::<Pg, _>(&query).to_string();
+        assert!(
+            sql.ends_with(r#" ORDER BY "deployment_schemas"."id", "subgraphs"."subgraph_version"."id" -- binds: []"#),
+            "{}",
+            sql
+        );
+
+        let paging = Paging {
+            order: DeploymentOrder::Name,
+            limit: Some(10),
+            offset: Some(20),
+        };
+        let query = paging.apply(search.query(&[]));
+        let sql = debug_query::<Pg, _>(&query).to_string();
+        assert!(
+            sql.ends_with(
+                r#" ORDER BY "subgraphs"."subgraph"."name", "deployment_schemas"."id", "subgraphs"."subgraph_version"."id" LIMIT $1 OFFSET $2 -- binds: [10, 20]"#
+            ),
+            "{}",
+            sql
+        );
+    }
+
+    #[test]
+    fn capped_paging_fetches_one_extra_row() {
+        let (paging, max) = Paging::default().capped(100);
+        assert_eq!(Some(101), paging.limit);
+        assert_eq!(Some(100), max);
+
+        let explicit = Paging {
+            limit: Some(5000),
+            ..Paging::default()
+        };
+        let (paging, max) = explicit.capped(100);
+        assert_eq!(Some(5000), paging.limit);
+        assert_eq!(None, max);
+    }
+
+    #[test]
+    fn parse_deployment_order() {
+        assert_eq!(DeploymentOrder::Id, "id".parse().unwrap());
+        assert_eq!(DeploymentOrder::Name, "name".parse().unwrap());
+        assert!("lag".parse::<DeploymentOrder>().is_err());
+    }
+
+    #[test]
+    fn version_statuses_for_flags() {
+        assert!(version_statuses(false, false, false).is_empty());
+        assert_eq!(vec!["current"], version_statuses(true, false, false));
+        assert_eq!(vec!["pending"], version_statuses(false, true, false));
+        assert_eq!(
+            vec!["current", "pending"],
+            version_statuses(false, false, true)
+        );
+    }
+
+    #[test]
+    fn name_search_escapes_like_wildcards() {
+        let search = DeploymentSearch::Name {
+            name: "a_b%".to_string(),
+        };
+        let query = search.query(&[]);
+        let sql = debug_query::<Pg, _>(&query).to_string();
+        assert!(
+            sql.contains(r#"WHERE ("subgraphs"."subgraph"."name" ILIKE $1 ESCAPE $2)"#),
+            "{}",
+            sql
+        );
+        assert!(
+            sql.ends_with(r#"-- binds: ["%a\\_b\\%%", "\\"]"#),
+            "{}",
+            sql
+        );
+    }
+
+    #[test]
+    fn exact_name_search_compares_name() {
+        let search: DeploymentSearch = "=a_b".parse().unwrap();
+        assert!(
+            matches!(&search, DeploymentSearch::ExactName { name } if name == "a_b"),
+            "{:?}",
+            search
+        );
+        assert_eq!("=a_b", search.to_string());
+
+        let query = search.query(&[]);
+        let sql = debug_query::<Pg, _>(&query).to_string();
+        assert!(
+            sql.ends_with(r#"WHERE ("subgraphs"."subgraph"."name" = $1) -- binds: ["a_b"]"#),
+            "{}",
+            sql
+        );
+
+        let search: DeploymentSearch = "a_b".parse().unwrap();
+        assert!(
+            matches!(&search, DeploymentSearch::Name { name } if name == "a_b"),
+            "{:?}",
+            search
+        );
+    }
+
+    #[test]
+    fn escape_like_escapes_wildcards() {
+        assert_eq!("subgraph", escape_like("subgraph"));
+        assert_eq!("a\\_b", escape_like("a_b"));
+        assert_eq!("100\\%", escape_like("100%"));
+        assert_eq!("a\\\\b", escape_like("a\\b"));
+    }
+}